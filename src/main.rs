@@ -1,12 +1,25 @@
-use std::{iter::Peekable, str::Chars};
+use std::{
+    collections::{HashMap, VecDeque},
+    iter::Peekable,
+    str::Chars,
+};
 use thiserror::Error;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Expan,
     Module,
     Ident(String),
-    Quoted(String),
+    /// A run of literal text inside a quoted body, up to the next `#`
+    /// interpolation marker or the closing quote.
+    Text(String),
+    /// `#`, introducing a `#name`, `#key:name`, or `#(...)` reference.
+    Hash,
+    /// `:`, separating the key prefix from the symbol in `#key:name`.
+    Colon,
+    /// A `// ...` or `/* ... */` comment, including its delimiters. Only
+    /// produced when the lexer was built `with_comments(true)`.
+    Comment(String),
     LBrace,
     RBrace,
     LSquare,
@@ -27,9 +40,6 @@ impl Token {
             _ => Token::Ident(s.to_string()),
         }
     }
-    pub fn quoted(s: &str) -> Token {
-        Token::Quoted(s.to_string())
-    }
     pub fn body(s: &str) -> Token {
         Token::Body(s.to_string())
     }
@@ -37,32 +47,34 @@ impl Token {
 
 #[derive(Error, Debug)]
 pub enum LexError {
-    #[error("No matches")]
-    NoMatches,
+    #[error("unterminated string starting at {start:?}")]
+    UnterminatedString { start: Position },
+    #[error("unterminated block comment starting at {start:?}")]
+    UnterminatedBlockComment { start: Position },
+    #[error("unexpected character {ch:?} at {at:?}")]
+    UnexpectedChar { ch: char, at: Position },
 }
 
-/// Consumes bytes while a predicate evaluates to true.
-fn take_while<F>(data: &str, mut pred: F) -> Result<(&str, usize), LexError>
-where
-    F: FnMut(char) -> bool,
-{
-    let mut current_index = 0;
-
-    for ch in data.chars() {
-        let should_continue = pred(ch);
-
-        if !should_continue {
-            break;
-        }
+/// A 1-based line/column location paired with the byte offset it corresponds to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: u32,
+    pub col: u32,
+    pub offset: usize,
+}
 
-        current_index += ch.len_utf8();
-    }
+/// A token together with the source span it was lexed from.
+#[derive(Debug, PartialEq)]
+pub struct Spanned {
+    pub token: Token,
+    pub start: Position,
+    pub end: Position,
+}
 
-    if current_index == 0 {
-        Err(LexError::NoMatches)
-    } else {
-        Ok((&data[..current_index], current_index))
-    }
+/// Whether `ch` can start an identifier (`read_identifier`'s own rule),
+/// used to decide whether a `#`/`:` actually introduces a reference.
+fn is_ident_start(ch: Option<char>) -> bool {
+    matches!(ch, Some(c) if c.is_alphabetic() || c == '_')
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -72,13 +84,47 @@ pub enum LexerState {
     QuotedString,
 }
 
-struct Lexer<'a> {
+/// Where we are inside a `#name` / `#key:name` reference. Tracked
+/// separately from `LexerState` because a reference isn't bracketed: it
+/// ends as soon as its (optional) key-qualified identifier is read, rather
+/// than on a matching closing token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RefState {
+    None,
+    AfterHash,
+    AfterIdent,
+    AfterColon,
+}
+
+pub struct Lexer<'a> {
     input: &'a str,
     position: usize,
     read_position: usize,
     iterator: Peekable<Chars<'a>>,
     ch: Option<char>,
     state: Vec<LexerState>,
+    ref_state: RefState,
+    line: u32,
+    col: u32,
+    /// Whether comments are handed back as `Token::Comment` instead of
+    /// being discarded like whitespace.
+    emit_comments: bool,
+    /// Tokens produced ahead of the current position by `peek`/`peek_nth`,
+    /// not yet handed out by `next_token`.
+    lookahead: VecDeque<Spanned>,
+    /// Byte span of the token last returned by `next_token`, used by
+    /// `slice`/`remainder`.
+    current_span: Option<(usize, usize)>,
+    /// Where the currently open `"..."` body started, for reporting an
+    /// unterminated string.
+    quote_start: Option<Position>,
+    /// Lexical errors recovered from so far, in the order they occurred.
+    errors: Vec<LexError>,
+    /// Extra keywords registered via `LexerBuilder`, consulted by
+    /// `read_identifier` alongside the grammar's built-ins.
+    keywords: HashMap<String, Token>,
+    /// Extra single-character symbol tokens registered via `LexerBuilder`.
+    symbols: HashMap<char, Token>,
 }
 
 impl<'a> Lexer<'a> {
@@ -91,10 +137,27 @@ impl<'a> Lexer<'a> {
             ch: None,
             iterator,
             state: vec![LexerState::Outer],
+            ref_state: RefState::None,
+            line: 1,
+            col: 1,
+            emit_comments: false,
+            lookahead: VecDeque::new(),
+            current_span: None,
+            quote_start: None,
+            errors: Vec::new(),
+            keywords: HashMap::new(),
+            symbols: HashMap::new(),
         };
         lexer.read_char();
         lexer
     }
+
+    /// Keep comments as `Token::Comment` instead of discarding them, for
+    /// tools that want to round-trip or format grammar files.
+    pub fn with_comments(mut self, emit_comments: bool) -> Self {
+        self.emit_comments = emit_comments;
+        self
+    }
     /// Advance past whitespace.
     pub fn advance_whitespace(&mut self) {
         while let Some(ch) = self.ch {
@@ -104,28 +167,43 @@ impl<'a> Lexer<'a> {
             self.read_char();
         }
     }
-    /// Read a character and advance the read position.
+    /// Read a character and advance the read position, keeping `line`/`col`
+    /// in step with whatever character is being passed over. `read_position`
+    /// tracks byte offsets, not char counts, so it advances by the new
+    /// char's UTF-8 width rather than by 1 — otherwise anything past the
+    /// first multi-byte character would panic on a non-char-boundary index.
     fn read_char(&mut self) {
+        if let Some(ch) = self.ch {
+            if ch == '\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
+        }
         if self.read_position >= self.input.len() {
             self.ch = None;
         } else {
             self.ch = self.iterator.next();
         }
         self.position = self.read_position;
-        self.read_position += 1;
+        self.read_position += self.ch.map_or(1, |ch| ch.len_utf8());
     }
 
-    fn read_string(&mut self) -> Token {
-        self.read_char();
-        let lhs = self.position;
-        loop {
-            match self.ch {
-                Some('"') | None => break,
-                _ => (),
-            }
-            self.read_char();
+    /// The current line/column/byte-offset location.
+    fn position_marker(&self) -> Position {
+        Position {
+            line: self.line,
+            col: self.col,
+            offset: self.position,
         }
-        Token::quoted(&self.input[lhs..self.position])
+    }
+
+    fn current_state(&self) -> LexerState {
+        *self
+            .state
+            .last()
+            .expect("lexer state stack is never empty")
     }
 
     fn read_identifier(&mut self) -> Token {
@@ -138,25 +216,415 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        Token::ident(&self.input[position..self.position])
+        let word = &self.input[position..self.position];
+        self.keywords
+            .get(word)
+            .cloned()
+            .unwrap_or_else(|| Token::ident(word))
     }
 
-    pub fn next_token(&mut self) -> Token {
-        self.advance_whitespace();
-        let tok = match self.ch {
-            Some(',') => Token::Comma,
-            Some('(') => Token::LParen,
-            Some(')') => Token::RParen,
-            Some('{') => Token::LBrace,
-            Some('}') => Token::RBrace,
-            Some('[') => Token::LSquare,
-            Some(']') => Token::RSquare,
-            Some('"') => self.read_string(),
-            Some(_) => self.read_identifier(),
-            None => Token::EOF,
+    /// Reads a run of literal text up to the next interpolation marker or
+    /// the closing quote, without consuming either. A `#` that isn't
+    /// actually followed by an identifier or `(` isn't a marker, so it's
+    /// swallowed into the text run rather than ending it.
+    fn read_text_run(&mut self) -> String {
+        let position = self.position;
+        loop {
+            let ch = self.ch;
+            match ch {
+                None | Some('"') => break,
+                Some('#') => {
+                    let next = self.peek_char();
+                    if next == Some('(') || is_ident_start(next) {
+                        break;
+                    }
+                    self.read_char();
+                }
+                _ => self.read_char(),
+            }
+        }
+        self.input[position..self.position].to_string()
+    }
+
+    /// The character after `self.ch`, without consuming anything.
+    fn peek_char(&mut self) -> Option<char> {
+        self.iterator.peek().copied()
+    }
+
+    /// Reads a `// ...` comment up to (but not including) the newline.
+    fn read_line_comment(&mut self) -> String {
+        let position = self.position;
+        self.read_char();
+        self.read_char();
+        while !matches!(self.ch, None | Some('\n')) {
+            self.read_char();
+        }
+        self.input[position..self.position].to_string()
+    }
+
+    /// Reads a `/* ... */` comment, honouring nesting. Returns whether it
+    /// was properly closed before EOF.
+    fn read_block_comment(&mut self) -> (String, bool) {
+        let position = self.position;
+        self.read_char();
+        self.read_char();
+        let mut depth = 1;
+        let terminated = loop {
+            let ch = self.ch;
+            match ch {
+                None => break false,
+                Some('*') if self.peek_char() == Some('/') => {
+                    self.read_char();
+                    self.read_char();
+                    depth -= 1;
+                    if depth == 0 {
+                        break true;
+                    }
+                }
+                Some('/') if self.peek_char() == Some('*') => {
+                    self.read_char();
+                    self.read_char();
+                    depth += 1;
+                }
+                _ => self.read_char(),
+            }
         };
+        (self.input[position..self.position].to_string(), terminated)
+    }
+
+    /// Handles `#`, whether it introduces a bare reference (`#name`,
+    /// `#key:name`) or a modifier call (`#(...)`). Shared between quoted
+    /// text and nested modifier-call bodies, since `#(cap #name)` lexes
+    /// `#name` the same way it would directly inside a string. Inside a
+    /// quoted body it's only reached for an actual marker, since any other
+    /// `#` there is read as text. Elsewhere, a `#` not followed by an
+    /// identifier or `(` is returned as a bare `Hash` and the
+    /// reference-state machine is never entered, so lexing carries on with
+    /// the next token as usual.
+    fn read_hash(&mut self) -> Token {
         self.read_char();
-        tok
+        if self.ch == Some('(') {
+            self.state.push(LexerState::ExpanList);
+        } else if is_ident_start(self.ch) {
+            self.ref_state = RefState::AfterHash;
+        }
+        Token::Hash
+    }
+
+    /// Lexes the identifier/colon tokens of a `#name` or `#key:name`
+    /// reference, one token per call, resuming normal lexing once it's
+    /// fully read.
+    fn next_reference_token(&mut self) -> Spanned {
+        let start = self.position_marker();
+        let tok = match self.ref_state {
+            RefState::AfterHash => {
+                let ident = self.read_identifier();
+                self.ref_state = if self.ch == Some(':') {
+                    RefState::AfterIdent
+                } else {
+                    RefState::None
+                };
+                ident
+            }
+            RefState::AfterIdent => {
+                self.read_char();
+                self.ref_state = if is_ident_start(self.ch) {
+                    RefState::AfterColon
+                } else {
+                    RefState::None
+                };
+                Token::Colon
+            }
+            RefState::AfterColon => {
+                self.ref_state = RefState::None;
+                self.read_identifier()
+            }
+            RefState::None => unreachable!("next_reference_token called outside a reference"),
+        };
+        Spanned {
+            token: tok,
+            start,
+            end: self.position_marker(),
+        }
+    }
+
+    /// Lexes one token while inside a `"..."` body: either a literal `Text`
+    /// run, or the start of a `#` interpolation.
+    fn next_quoted_token(&mut self) -> Spanned {
+        let start = self.position_marker();
+        let tok = match self.ch {
+            Some('"') => {
+                self.read_char();
+                self.state.pop();
+                self.quote_start = None;
+                return self.produce();
+            }
+            None => {
+                if let Some(quote_start) = self.quote_start.take() {
+                    self.errors
+                        .push(LexError::UnterminatedString { start: quote_start });
+                }
+                self.state.pop();
+                Token::Text(String::new())
+            }
+            Some('#') => {
+                let next = self.peek_char();
+                if next == Some('(') || is_ident_start(next) {
+                    self.read_hash()
+                } else {
+                    Token::Text(self.read_text_run())
+                }
+            }
+            _ => Token::Text(self.read_text_run()),
+        };
+        Spanned {
+            token: tok,
+            start,
+            end: self.position_marker(),
+        }
+    }
+
+    /// Lexes one token outside of a quoted body: grammar punctuation,
+    /// keywords/identifiers, the start of a quoted body, or (inside a
+    /// `#(...)` modifier call) a nested reference.
+    fn next_outer_token(&mut self) -> Spanned {
+        let start = loop {
+            self.advance_whitespace();
+            let start = self.position_marker();
+            if self.ch == Some('/') && self.peek_char() == Some('/') {
+                let text = self.read_line_comment();
+                if self.emit_comments {
+                    return Spanned {
+                        token: Token::Comment(text),
+                        start,
+                        end: self.position_marker(),
+                    };
+                }
+                continue;
+            }
+            if self.ch == Some('/') && self.peek_char() == Some('*') {
+                let (text, terminated) = self.read_block_comment();
+                if !terminated {
+                    self.errors
+                        .push(LexError::UnterminatedBlockComment { start });
+                }
+                if self.emit_comments {
+                    return Spanned {
+                        token: Token::Comment(text),
+                        start,
+                        end: self.position_marker(),
+                    };
+                }
+                continue;
+            }
+            break start;
+        };
+        let tok = match self.ch {
+            Some(',') => {
+                self.read_char();
+                Token::Comma
+            }
+            Some('(') => {
+                self.read_char();
+                Token::LParen
+            }
+            Some(')') => {
+                self.read_char();
+                if self.current_state() == LexerState::ExpanList {
+                    self.state.pop();
+                }
+                Token::RParen
+            }
+            Some('{') => {
+                self.read_char();
+                Token::LBrace
+            }
+            Some('}') => {
+                self.read_char();
+                Token::RBrace
+            }
+            Some('[') => {
+                self.read_char();
+                self.state.push(LexerState::ExpanList);
+                Token::LSquare
+            }
+            Some(']') => {
+                self.read_char();
+                if self.current_state() == LexerState::ExpanList {
+                    self.state.pop();
+                }
+                Token::RSquare
+            }
+            Some(':') => {
+                self.read_char();
+                Token::Colon
+            }
+            Some('#') => self.read_hash(),
+            Some('"') => {
+                self.quote_start = Some(start);
+                self.read_char();
+                self.state.push(LexerState::QuotedString);
+                return self.produce();
+            }
+            Some(ch) => match self.symbols.get(&ch).cloned() {
+                Some(tok) => {
+                    self.read_char();
+                    tok
+                }
+                None if ch.is_alphabetic() || ch == '_' => self.read_identifier(),
+                None => {
+                    self.errors.push(LexError::UnexpectedChar { ch, at: start });
+                    self.read_char();
+                    Token::Unknown(ch)
+                }
+            },
+            None => {
+                // EOF can be reached here instead of in `next_quoted_token` when a
+                // `#(...)` modifier call nested inside a quoted body is itself left
+                // open at end of input (state stack `[.., QuotedString, ExpanList]`).
+                // The open string's bookkeeping has to be settled here too, or the
+                // `UnterminatedString` error never gets recorded.
+                if let Some(quote_start) = self.quote_start.take() {
+                    self.errors
+                        .push(LexError::UnterminatedString { start: quote_start });
+                }
+                Token::EOF
+            }
+        };
+        Spanned {
+            token: tok,
+            start,
+            end: self.position_marker(),
+        }
+    }
+
+    /// Produces the next token straight from the input, ignoring the
+    /// lookahead buffer. Called by `next_token`/`peek_nth`, and by the
+    /// per-state lexers themselves when a quote opens or closes.
+    fn produce(&mut self) -> Spanned {
+        if self.ref_state != RefState::None {
+            return self.next_reference_token();
+        }
+        match self.current_state() {
+            LexerState::QuotedString => self.next_quoted_token(),
+            LexerState::ExpanList | LexerState::Outer => self.next_outer_token(),
+        }
+    }
+
+    /// Lex the next token, paired with the span it was read from. Drains
+    /// the lookahead buffer first so tokens already seen via `peek`/
+    /// `peek_nth` aren't produced twice.
+    pub fn next_token(&mut self) -> Spanned {
+        let spanned = self.lookahead.pop_front().unwrap_or_else(|| self.produce());
+        self.current_span = Some((spanned.start.offset, spanned.end.offset));
+        spanned
+    }
+
+    /// Backward-compatible shorthand for callers that only care about the
+    /// token kind and don't need its span.
+    pub fn next_token_kind(&mut self) -> Token {
+        self.next_token().token
+    }
+
+    /// Look at the next token without consuming it.
+    pub fn peek(&mut self) -> Option<&Spanned> {
+        self.peek_nth(0)
+    }
+
+    /// Look `n` tokens ahead (`peek_nth(0)` is the same as `peek`) without
+    /// consuming any of them. Buffers intervening tokens so repeated calls
+    /// don't re-lex the input.
+    pub fn peek_nth(&mut self, n: usize) -> Option<&Spanned> {
+        while self.lookahead.len() <= n {
+            let at_eof = matches!(
+                self.lookahead.back(),
+                Some(Spanned {
+                    token: Token::EOF,
+                    ..
+                })
+            );
+            if at_eof {
+                break;
+            }
+            let spanned = self.produce();
+            self.lookahead.push_back(spanned);
+        }
+        self.lookahead.get(n)
+    }
+
+    /// The source text of the token last returned by `next_token`.
+    pub fn slice(&self) -> &'a str {
+        match self.current_span {
+            Some((start, end)) => &self.input[start..end],
+            None => "",
+        }
+    }
+
+    /// The source text remaining after the token last returned by
+    /// `next_token`.
+    pub fn remainder(&self) -> &'a str {
+        match self.current_span {
+            Some((_, end)) => &self.input[end..],
+            None => self.input,
+        }
+    }
+
+    /// Lexical errors recovered from so far, in the order they occurred.
+    /// Populated as `next_token`/`peek`/`peek_nth` lex past them; check
+    /// after exhausting the lexer to report every problem in one pass.
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+}
+
+/// Builds a `Lexer` with extra keywords and single-character symbol
+/// tokens registered ahead of time, so an embedding application can
+/// extend the grammar's reserved words and delimiters without forking
+/// the lexer.
+#[derive(Debug, Default)]
+pub struct LexerBuilder {
+    keywords: HashMap<String, Token>,
+    symbols: HashMap<char, Token>,
+}
+
+impl LexerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `word` as a keyword, recognised by `read_identifier`
+    /// alongside the grammar's built-ins (`expan`, `mod`). Unregistered
+    /// identifiers still fall through to `Token::Ident` as today.
+    pub fn keyword(mut self, word: &str, token: Token) -> Self {
+        self.keywords.insert(word.to_string(), token);
+        self
+    }
+
+    /// Register `ch` as a single-character symbol token.
+    pub fn symbol(mut self, ch: char, token: Token) -> Self {
+        self.symbols.insert(ch, token);
+        self
+    }
+
+    /// Build a `Lexer` over `input` using the registered rules.
+    pub fn build(self, input: &str) -> Lexer<'_> {
+        let mut lexer = Lexer::new(input);
+        lexer.keywords = self.keywords;
+        lexer.symbols = self.symbols;
+        lexer
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Spanned;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let spanned = self.next_token();
+        if spanned.token == Token::EOF {
+            None
+        } else {
+            Some(spanned)
+        }
     }
 }
 
@@ -181,12 +649,274 @@ fn main() {
         ]
     }
     "#;
-    let mut lex = Lexer::new(stmt);
-    loop {
-        let token = lex.next_token();
-        if token == Token::EOF {
-            break;
-        }
-        println!("{:?}", token);
+    let lex = Lexer::new(stmt);
+    for spanned in lex {
+        println!("{:?}", spanned);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<Token> {
+        Lexer::new(input).map(|s| s.token).collect()
+    }
+
+    #[test]
+    fn text_and_reference_interleave() {
+        assert_eq!(
+            tokens(r#""foo #name bar""#),
+            vec![
+                Token::Text("foo ".to_string()),
+                Token::Hash,
+                Token::Ident("name".to_string()),
+                Token::Text(" bar".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_qualified_reference() {
+        assert_eq!(
+            tokens(r##""#key:name""##),
+            vec![
+                Token::Hash,
+                Token::Ident("key".to_string()),
+                Token::Colon,
+                Token::Ident("name".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn bare_hash_stays_literal_text() {
+        assert_eq!(
+            tokens(r#""stray # mark""#),
+            vec![Token::Text("stray # mark".to_string())]
+        );
+    }
+
+    #[test]
+    fn trailing_key_colon_without_name_falls_back_to_outer_lexing() {
+        assert_eq!(
+            tokens(r##""#key:""##),
+            vec![
+                Token::Hash,
+                Token::Ident("key".to_string()),
+                Token::Colon,
+            ]
+        );
+    }
+
+    #[test]
+    fn unexpected_char_is_recorded_and_recovered() {
+        let mut lex = Lexer::new("@ mod");
+        let toks: Vec<Token> = (&mut lex).map(|s| s.token).collect();
+        assert_eq!(toks, vec![Token::Unknown('@'), Token::Module]);
+        assert!(matches!(
+            lex.errors(),
+            [LexError::UnexpectedChar { ch: '@', .. }]
+        ));
+    }
+
+    #[test]
+    fn unterminated_block_comment_recovers_to_eof() {
+        let mut lex = Lexer::new("/* never closed");
+        let toks: Vec<Token> = (&mut lex).map(|s| s.token).collect();
+        assert_eq!(toks, vec![]);
+        assert!(matches!(
+            lex.errors(),
+            [LexError::UnterminatedBlockComment { .. }]
+        ));
+    }
+
+    #[test]
+    fn unterminated_string_recovers_with_empty_text_then_eof() {
+        let mut lex = Lexer::new(r#""unterminated"#);
+        let toks: Vec<Token> = (&mut lex).map(|s| s.token).collect();
+        assert_eq!(
+            toks,
+            vec![
+                Token::Text("unterminated".to_string()),
+                Token::Text(String::new()),
+            ]
+        );
+        assert!(matches!(
+            lex.errors(),
+            [LexError::UnterminatedString { .. }]
+        ));
+    }
+
+    #[test]
+    fn unterminated_string_with_open_modifier_call_still_reports_error() {
+        // EOF hits while the state stack is `[Outer, QuotedString, ExpanList]`,
+        // so it's `next_outer_token`'s `None` arm that has to notice the open
+        // string, not `next_quoted_token`'s.
+        let mut lex = Lexer::new(r##""#(cap"##);
+        let _: Vec<Token> = (&mut lex).map(|s| s.token).collect();
+        assert!(matches!(
+            lex.errors(),
+            [LexError::UnterminatedString { .. }]
+        ));
+    }
+
+    #[test]
+    fn position_tracks_line_and_col_across_newlines() {
+        let mut lex = Lexer::new("mod\nfoo");
+        let module = lex.next_token();
+        assert_eq!(
+            module.start,
+            Position {
+                line: 1,
+                col: 1,
+                offset: 0
+            }
+        );
+        let ident = lex.next_token();
+        assert_eq!(ident.token, Token::Ident("foo".to_string()));
+        assert_eq!(
+            ident.start,
+            Position {
+                line: 2,
+                col: 1,
+                offset: 4
+            }
+        );
+    }
+
+    #[test]
+    fn position_offsets_are_byte_offsets_not_char_counts() {
+        let mut lex = Lexer::new("mod café");
+        lex.next_token();
+        let ident = lex.next_token();
+        assert_eq!(ident.token, Token::Ident("café".to_string()));
+        assert_eq!(ident.start.offset, 4);
+        assert_eq!(ident.end.offset, 9);
+    }
+
+    #[test]
+    fn peek_nth_looks_ahead_without_consuming() {
+        let mut lex = Lexer::new("mod foo {");
+        assert_eq!(lex.peek().map(|s| &s.token), Some(&Token::Module));
+        assert_eq!(
+            lex.peek_nth(1).map(|s| &s.token),
+            Some(&Token::Ident("foo".to_string()))
+        );
+        assert_eq!(lex.peek_nth(2).map(|s| &s.token), Some(&Token::LBrace));
+        // Peeking doesn't consume: next_token still starts from the front.
+        assert_eq!(lex.next_token().token, Token::Module);
+        assert_eq!(lex.next_token().token, Token::Ident("foo".to_string()));
+        assert_eq!(lex.next_token().token, Token::LBrace);
+    }
+
+    #[test]
+    fn slice_and_remainder_track_the_last_token() {
+        let mut lex = Lexer::new("mod foo");
+        lex.next_token();
+        assert_eq!(lex.slice(), "mod");
+        assert_eq!(lex.remainder(), " foo");
+        lex.next_token();
+        assert_eq!(lex.slice(), "foo");
+        assert_eq!(lex.remainder(), "");
+    }
+
+    #[test]
+    fn iterator_impl_stops_at_eof() {
+        let toks: Vec<Token> = Lexer::new("mod foo").map(|s| s.token).collect();
+        assert_eq!(
+            toks,
+            vec![Token::Module, Token::Ident("foo".to_string())]
+        );
+    }
+
+    #[test]
+    fn line_comment_is_discarded_by_default() {
+        assert_eq!(tokens("mod // trailing comment\nfoo"), {
+            vec![Token::Module, Token::Ident("foo".to_string())]
+        });
+    }
+
+    #[test]
+    fn nested_block_comment_is_discarded_by_default() {
+        assert_eq!(
+            tokens("mod /* outer /* inner */ still outer */ foo"),
+            vec![Token::Module, Token::Ident("foo".to_string())]
+        );
+    }
+
+    #[test]
+    fn unterminated_nested_block_comment_is_reported() {
+        let mut lex = Lexer::new("mod /* outer /* inner */ foo");
+        let toks: Vec<Token> = (&mut lex).map(|s| s.token).collect();
+        assert_eq!(toks, vec![Token::Module]);
+        assert!(matches!(
+            lex.errors(),
+            [LexError::UnterminatedBlockComment { .. }]
+        ));
+    }
+
+    #[test]
+    fn with_comments_emits_comment_tokens() {
+        let toks: Vec<Token> = LexerBuilder::new()
+            .build("mod // trailing\nfoo")
+            .with_comments(true)
+            .map(|s| s.token)
+            .collect();
+        assert_eq!(
+            toks,
+            vec![
+                Token::Module,
+                Token::Comment("// trailing".to_string()),
+                Token::Ident("foo".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn comment_syntax_inside_a_quoted_body_stays_literal_text() {
+        assert_eq!(
+            tokens(r#""// not a comment /* also not */""#),
+            vec![Token::Text("// not a comment /* also not */".to_string())]
+        );
+    }
+
+    #[test]
+    fn builder_registers_custom_keyword() {
+        let toks: Vec<Token> = LexerBuilder::new()
+            .keyword("let", Token::Expan)
+            .build("let x")
+            .map(|s| s.token)
+            .collect();
+        assert_eq!(
+            toks,
+            vec![
+                Token::Expan,
+                Token::Ident("x".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn builder_registers_custom_symbol() {
+        let toks: Vec<Token> = LexerBuilder::new()
+            .symbol('@', Token::Hash)
+            .build("@ mod")
+            .map(|s| s.token)
+            .collect();
+        assert_eq!(toks, vec![Token::Hash, Token::Module]);
+    }
+
+    #[test]
+    fn unregistered_identifiers_and_symbols_still_fall_through() {
+        let mut lex = LexerBuilder::new()
+            .keyword("let", Token::Ident("let".to_string()))
+            .build("@ bar");
+        let toks: Vec<Token> = (&mut lex).map(|s| s.token).collect();
+        assert_eq!(toks, vec![Token::Unknown('@'), Token::Ident("bar".to_string())]);
+        assert!(matches!(
+            lex.errors(),
+            [LexError::UnexpectedChar { ch: '@', .. }]
+        ));
     }
 }